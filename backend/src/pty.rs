@@ -4,7 +4,7 @@ use eyre::{eyre, Result};
 use portable_pty::{CommandBuilder, MasterPty, PtySize};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     io::Write,
     sync::{Arc, Mutex},
 };
@@ -18,6 +18,24 @@ use uuid::Uuid;
 
 use crate::runtime::pty_store::PtyLike;
 
+/// Grace period between asking a process group to terminate and forcing it to die.
+const KILL_GRACE_PERIOD: Duration = Duration::from_millis(500);
+
+/// Where a runbook block's shell actually runs. Threaded through `PtyMetadata` so the store
+/// can decide whether to hand a block to `Pty::open` or `SshPty::open`.
+#[derive(Clone, Deserialize, Serialize, Debug, TS, Default, PartialEq, Eq)]
+#[ts(export)]
+#[serde(tag = "type")]
+pub enum PtyTarget {
+    #[default]
+    Local,
+    Ssh {
+        host: String,
+        user: String,
+        port: Option<u16>,
+    },
+}
+
 #[derive(Clone, Deserialize, Serialize, Debug, TS)]
 #[ts(export)]
 pub struct PtyMetadata {
@@ -25,6 +43,8 @@ pub struct PtyMetadata {
     pub runbook: Uuid,
     pub block: String,
     pub created_at: u64,
+    #[serde(default)]
+    pub target: PtyTarget,
 }
 
 pub struct Pty {
@@ -34,6 +54,22 @@ pub struct Pty {
     pub master: Arc<Mutex<Box<dyn MasterPty + Send>>>,
     pub reader: Arc<Mutex<Box<dyn std::io::Read + Send>>>,
     pub child: Arc<Mutex<Box<dyn portable_pty::Child + Send>>>,
+
+    /// Job Object the child was placed into on Windows, so that `kill_child` can terminate the
+    /// whole tree it spawned rather than just the shell. On Unix, `kill_child` instead walks the
+    /// shell's live descendants each time it's called (see `descendant_process_groups`), since
+    /// with job control on, each job the shell launches gets its own process group distinct
+    /// from the shell's.
+    #[cfg(windows)]
+    job: Arc<Mutex<Option<windows_sys::Win32::Foundation::HANDLE>>>,
+
+    /// Resolves to the child's exit status as soon as the reaper task observes it. On Linux the
+    /// reaper waits on a pidfd or SIGCHLD; elsewhere it polls `try_wait`. See `spawn_reaper`.
+    exit_rx: tokio::sync::watch::Receiver<Option<portable_pty::ExitStatus>>,
+
+    /// Flips to `true` the first time the shell-integration hook's OSC 133 prompt marker is
+    /// seen on the pty output. See `PromptMarkerTee`.
+    ready_rx: tokio::sync::watch::Receiver<bool>,
 }
 
 #[async_trait]
@@ -53,6 +89,26 @@ impl PtyLike for Pty {
     async fn resize(&self, rows: u16, cols: u16) -> Result<()> {
         self.resize(rows, cols).await
     }
+
+    async fn wait(&self) -> Result<portable_pty::ExitStatus> {
+        self.wait().await
+    }
+
+    async fn interrupt(&self) -> Result<()> {
+        self.interrupt().await
+    }
+
+    async fn terminate(&self) -> Result<()> {
+        self.terminate().await
+    }
+
+    async fn suspend(&self) -> Result<()> {
+        self.suspend().await
+    }
+
+    async fn resume(&self) -> Result<()> {
+        self.resume().await
+    }
 }
 
 impl Pty {
@@ -102,6 +158,15 @@ impl Pty {
         };
         drop(pair.slave);
 
+        #[cfg(windows)]
+        let job = match child.process_id() {
+            Some(pid) => Some(assign_child_to_job(pid)?),
+            None => None,
+        };
+
+        let child = Arc::new(Mutex::new(child));
+        let exit_rx = spawn_reaper(Arc::clone(&child));
+
         // Handle input -> write to master writer
         let (master_tx, mut master_rx) = tokio::sync::mpsc::channel::<Bytes>(32);
 
@@ -112,6 +177,18 @@ impl Pty {
             .map_err(|e| e.to_string())
             .expect("Failed to clone reader");
 
+        // Scan for the shell-integration hook's OSC 133 prompt markers by wrapping the single
+        // reader rather than cloning a second one off the master: on Unix `try_clone_reader`
+        // dups the underlying fd, so two independent readers would race over the same pty
+        // bytes, each stealing output the other needed (corrupting what the frontend sees and
+        // risking the scanner missing the marker entirely).
+        let (ready_tx, ready_rx) = tokio::sync::watch::channel(false);
+        let reader: Box<dyn std::io::Read + Send> = Box::new(PromptMarkerTee {
+            inner: reader,
+            scanner: OscMarkerScanner::default(),
+            ready_tx,
+        });
+
         tokio::spawn(async move {
             while let Some(bytes) = master_rx.recv().await {
                 writer.write_all(&bytes).unwrap();
@@ -130,10 +207,31 @@ impl Pty {
             tx: master_tx,
             master: Arc::new(Mutex::new(pair.master)),
             reader: Arc::new(Mutex::new(reader)),
-            child: Arc::new(Mutex::new(child)),
+            child,
+            #[cfg(windows)]
+            job: Arc::new(Mutex::new(job)),
+            exit_rx,
+            ready_rx,
         })
     }
 
+    /// Wait for the child shell to exit and return its exit status (or terminating signal, on
+    /// Unix). Multiple callers may await this concurrently; all of them resolve once the child
+    /// exits.
+    pub async fn wait(&self) -> Result<portable_pty::ExitStatus> {
+        let mut rx = self.exit_rx.clone();
+
+        loop {
+            if let Some(status) = rx.borrow().clone() {
+                return Ok(status);
+            }
+
+            rx.changed()
+                .await
+                .map_err(|_| eyre!("Exit status watcher dropped before the child exited"))?;
+        }
+    }
+
     pub async fn resize(&self, rows: u16, cols: u16) -> Result<()> {
         let master = self
             .master
@@ -159,6 +257,73 @@ impl Pty {
             .map_err(|e| eyre!("Failed to write to master tx: {}", e))
     }
 
+    /// Deliver `SIGINT` to the terminal's foreground process group, same as the user pressing
+    /// Ctrl-C at the keyboard: it stops whatever `block` command is currently running while
+    /// leaving the interactive shell itself alive at its prompt.
+    pub async fn interrupt(&self) -> Result<()> {
+        self.send_signal(libc::SIGINT).await
+    }
+
+    /// Deliver `SIGTERM` to the terminal's foreground process group.
+    pub async fn terminate(&self) -> Result<()> {
+        self.send_signal(libc::SIGTERM).await
+    }
+
+    /// Deliver `SIGTSTP` to the terminal's foreground process group, suspending the running
+    /// command the same way Ctrl-Z would. We target the foreground group rather than the shell
+    /// (session leader) because session leaders do not themselves suspend on `SIGTSTP`.
+    pub async fn suspend(&self) -> Result<()> {
+        self.send_signal(libc::SIGTSTP).await
+    }
+
+    /// Deliver `SIGCONT` to the terminal's foreground process group, resuming a command
+    /// previously suspended with `suspend`.
+    pub async fn resume(&self) -> Result<()> {
+        self.send_signal(libc::SIGCONT).await
+    }
+
+    /// Signal whatever process group currently owns the terminal (the running `block` command,
+    /// if any), rather than the shell itself, by reading the foreground pgid off the pty master
+    /// with `tcgetpgrp`.
+    #[cfg(unix)]
+    async fn send_signal(&self, sig: libc::c_int) -> Result<()> {
+        let fd = {
+            let master = self
+                .master
+                .lock()
+                .map_err(|e| eyre!("Failed to lock pty master: {e}"))?;
+
+            master
+                .as_raw_fd()
+                .ok_or_else(|| eyre!("Pty master has no raw fd to read the foreground pgid from"))?
+        };
+
+        // SAFETY: `fd` is the pty master's own raw fd, valid for the lifetime of this call
+        // since we hold no reference to it past this block.
+        let pgid = unsafe { libc::tcgetpgrp(fd) };
+        if pgid == -1 {
+            return Err(eyre!(
+                "Failed to read foreground process group: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+
+        // SAFETY: `pgid` was just read from our own pty's controlling terminal.
+        if unsafe { libc::killpg(pgid, sig) } != 0 {
+            return Err(eyre!(
+                "Failed to signal foreground process group: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    async fn send_signal(&self, _sig: i32) -> Result<()> {
+        Err(eyre!("Job-control signals are not supported on this platform"))
+    }
+
     #[allow(dead_code)]
     pub async fn send_string(&self, cmd: &str) -> Result<()> {
         let bytes: Vec<u8> = cmd.bytes().collect();
@@ -167,7 +332,40 @@ impl Pty {
         self.send_bytes(bytes).await
     }
 
+    /// Wait for the shell to reach its first prompt.
+    ///
+    /// Prefers the exact, per-prompt OSC 133 marker the shell-integration hook
+    /// (`ATUIN_DESKTOP_PTY`) is expected to emit when it draws a prompt; only falls back to the
+    /// old no-children heuristic (an expensive, racy scan of the whole process table) if no
+    /// marker arrives within the timeout. That fallback firing on every block is *expected* for
+    /// any shell that doesn't source our integration, but if it fires for every block on a shell
+    /// that's supposed to be integrated, that's a sign the integration script isn't emitting
+    /// `133;A`/`133;B` and is worth checking directly — hence the `warn!`, not `debug!`, below.
     pub async fn wait_for_shell_ready(&self) -> Result<()> {
+        const MARKER_TIMEOUT: Duration = Duration::from_secs(5);
+
+        let mut ready_rx = self.ready_rx.clone();
+        if *ready_rx.borrow() {
+            return Ok(());
+        }
+
+        match tokio::time::timeout(MARKER_TIMEOUT, ready_rx.changed()).await {
+            Ok(Ok(())) if *ready_rx.borrow() => {
+                log::debug!("Shell ready via OSC 133 prompt marker");
+                return Ok(());
+            }
+            _ => {
+                log::warn!(
+                    "No OSC 133 prompt marker seen within {:?}, falling back to child-polling heuristic",
+                    MARKER_TIMEOUT
+                );
+            }
+        }
+
+        self.wait_for_shell_ready_heuristic().await
+    }
+
+    async fn wait_for_shell_ready_heuristic(&self) -> Result<()> {
         const REQUIRED_READY_CHECKS: i32 = 3;
         let start = Instant::now();
         let mut consecutive_ready_checks = 0;
@@ -287,7 +485,84 @@ impl Pty {
         self.send_bytes(bytes).await
     }
 
+    /// Kill the shell and everything it spawned, not just the shell process itself.
+    ///
+    /// On Unix this signals every process group found in the shell's process tree, the shell's
+    /// own and each job's (`SIGTERM`, then `SIGKILL` after a short grace period if anything is
+    /// still alive).
+    /// On Windows the shell's Job Object is terminated, which kills every process nested
+    /// under it.
     pub async fn kill_child(&self) -> Result<()> {
+        #[cfg(unix)]
+        {
+            let shell_pid = {
+                let child = self
+                    .child
+                    .lock()
+                    .map_err(|e| eyre!("Failed to lock pty child: {e}"))?;
+                child.process_id()
+            };
+
+            if let Some(shell_pid) = shell_pid {
+                // The shell runs with `-i`, so job control is on: every job it launches
+                // (`make`, `docker`, `ssh`, ...) gets `setpgid`'d into its *own* foreground
+                // process group, distinct from the shell's. Signalling only the shell's group
+                // leaves those running, so walk the whole process tree rooted at the shell and
+                // signal every distinct group found in it.
+                let pgids = self.descendant_process_groups(shell_pid).await?;
+
+                // SAFETY: every pgid here was read off a descendant of our own shell, so
+                // signalling it cannot affect processes outside our own child tree.
+                for &pgid in &pgids {
+                    unsafe {
+                        libc::killpg(pgid, libc::SIGTERM);
+                    }
+                }
+
+                sleep(KILL_GRACE_PERIOD).await;
+
+                // Signal 0 only probes whether the group still has any member.
+                let any_group_alive = pgids
+                    .iter()
+                    .any(|&pgid| unsafe { libc::killpg(pgid, 0) } == 0);
+
+                if self.is_child_running()? || any_group_alive {
+                    for &pgid in &pgids {
+                        unsafe {
+                            libc::killpg(pgid, libc::SIGKILL);
+                        }
+                    }
+                }
+
+                return Ok(());
+            }
+        }
+
+        #[cfg(windows)]
+        {
+            let job = *self
+                .job
+                .lock()
+                .map_err(|e| eyre!("Failed to lock pty job: {e}"))?;
+
+            if let Some(job) = job {
+                use windows_sys::Win32::System::JobObjects::TerminateJobObject;
+
+                // SAFETY: `job` is a Job Object handle we created and own in `Pty::open`.
+                let ok = unsafe { TerminateJobObject(job, 1) };
+                if ok == 0 {
+                    return Err(eyre!(
+                        "Failed to terminate job object: {}",
+                        std::io::Error::last_os_error()
+                    ));
+                }
+
+                return Ok(());
+            }
+        }
+
+        // Fall back to killing just the shell process if it has no pid to walk its process
+        // tree from (Unix) or no job object was created for it (Windows).
         let mut child = self
             .child
             .lock()
@@ -299,4 +574,381 @@ impl Pty {
 
         Ok(())
     }
+
+    /// Walk the live process tree rooted at `shell_pid` and collect the distinct process groups
+    /// found in it, including the shell's own.
+    ///
+    /// With job control on, each job the shell launches is `setpgid`'d into its own group, so the
+    /// shell's descendants don't all share its pgid the way they would without `-i`. Signalling
+    /// just the shell's group misses them; this walk is what lets `kill_child` reach them too.
+    #[cfg(unix)]
+    async fn descendant_process_groups(&self, shell_pid: u32) -> Result<HashSet<libc::pid_t>> {
+        spawn_blocking(move || {
+            let mut sys = System::new();
+            sys.refresh_processes(ProcessesToUpdate::All, true);
+
+            let shell_pid = Pid::from_u32(shell_pid);
+
+            // Build the parent -> children adjacency, then BFS out from the shell to find every
+            // live descendant.
+            let mut children_of: HashMap<Pid, Vec<Pid>> = HashMap::new();
+            for (pid, process) in sys.processes() {
+                if let Some(parent_pid) = process.parent() {
+                    children_of.entry(parent_pid).or_default().push(*pid);
+                }
+            }
+
+            let mut descendants = vec![shell_pid];
+            let mut queue = std::collections::VecDeque::from([shell_pid]);
+            while let Some(pid) = queue.pop_front() {
+                if let Some(children) = children_of.get(&pid) {
+                    for &child_pid in children {
+                        descendants.push(child_pid);
+                        queue.push_back(child_pid);
+                    }
+                }
+            }
+
+            let mut pgids = HashSet::new();
+            for pid in descendants {
+                // SAFETY: `getpgid` is a plain read of process state; a pid that's already
+                // exited by the time we call it just yields ESRCH, which we ignore.
+                let pgid = unsafe { libc::getpgid(pid.as_u32() as libc::pid_t) };
+                if pgid > 0 {
+                    pgids.insert(pgid);
+                }
+            }
+
+            Ok(pgids)
+        })
+        .await
+        .map_err(|e| eyre!("Task join error: {}", e))?
+    }
+
+    #[cfg(unix)]
+    fn is_child_running(&self) -> Result<bool> {
+        let mut child = self
+            .child
+            .lock()
+            .map_err(|e| eyre!("Failed to lock pty child: {e}"))?;
+
+        match child.try_wait() {
+            Ok(Some(_)) => Ok(false),
+            Ok(None) => Ok(true),
+            Err(e) => Err(eyre!("Failed to check child process status: {}", e)),
+        }
+    }
+}
+
+#[cfg(windows)]
+fn assign_child_to_job(pid: u32) -> Result<windows_sys::Win32::Foundation::HANDLE> {
+    use windows_sys::Win32::{
+        Foundation::CloseHandle,
+        System::{
+            JobObjects::{AssignProcessToJobObject, CreateJobObjectW},
+            Threading::{OpenProcess, PROCESS_SET_QUOTA, PROCESS_TERMINATE},
+        },
+    };
+
+    // SAFETY: FFI calls into the Win32 Job Object API with owned, checked handles.
+    unsafe {
+        let job = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+        if job.is_null() {
+            return Err(eyre!(
+                "Failed to create job object: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+
+        let process = OpenProcess(PROCESS_SET_QUOTA | PROCESS_TERMINATE, 0, pid);
+        if process.is_null() {
+            CloseHandle(job);
+            return Err(eyre!(
+                "Failed to open child process: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+
+        let assigned = AssignProcessToJobObject(job, process);
+        CloseHandle(process);
+
+        if assigned == 0 {
+            CloseHandle(job);
+            return Err(eyre!(
+                "Failed to assign child process to job object: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+
+        Ok(job)
+    }
+}
+
+type ChildHandle = Arc<Mutex<Box<dyn portable_pty::Child + Send>>>;
+
+/// Reap the child in the background and publish its exit status.
+///
+/// On Linux we prefer registering the child's pidfd with the async reactor and waking up only
+/// when it becomes readable (i.e. the child has exited); this is the same approach
+/// `async-process` uses for its waitable backend. If pidfd isn't available (older kernels) we
+/// fall back to a SIGCHLD-driven wait. On other platforms there's no portable equivalent, so we
+/// fall back to polling `try_wait` (see `wait_blocking`).
+fn spawn_reaper(
+    child: ChildHandle,
+) -> tokio::sync::watch::Receiver<Option<portable_pty::ExitStatus>> {
+    let (tx, rx) = tokio::sync::watch::channel(None);
+
+    #[cfg(target_os = "linux")]
+    {
+        let pid = child.lock().ok().and_then(|c| c.process_id());
+
+        if let Some(pid) = pid.and_then(open_pidfd) {
+            tokio::spawn(wait_via_pidfd(pid, child, tx));
+            return rx;
+        }
+
+        tokio::spawn(wait_via_sigchld(child, tx));
+        return rx;
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        tokio::spawn(wait_blocking(child, tx));
+    }
+
+    rx
+}
+
+/// Used on macOS/Windows, and as the last step of the Linux pidfd path once the child is
+/// already known to have exited.
+///
+/// This deliberately does *not* call the blocking `Child::wait()` while holding the mutex:
+/// `kill_child` and the job-control signal methods also need `self.child.lock()`, and holding
+/// the guard across a blocking wait for the whole life of the shell would starve them until the
+/// shell exits on its own. Instead we poll `try_wait`, releasing the lock between checks.
+async fn wait_blocking(
+    child: ChildHandle,
+    tx: tokio::sync::watch::Sender<Option<portable_pty::ExitStatus>>,
+) {
+    const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+    loop {
+        let status = {
+            let mut child = match child.lock() {
+                Ok(child) => child,
+                Err(_) => return,
+            };
+            child.try_wait()
+        };
+
+        match status {
+            Ok(Some(status)) => {
+                let _ = tx.send(Some(status));
+                return;
+            }
+            Ok(None) => sleep(POLL_INTERVAL).await,
+            Err(_) => return,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn open_pidfd(pid: u32) -> Option<std::os::fd::OwnedFd> {
+    use std::os::fd::FromRawFd;
+
+    // SAFETY: `pidfd_open` returns either a valid owned fd or a negative errno; we only
+    // construct an `OwnedFd` in the former case.
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+
+    if fd < 0 {
+        return None;
+    }
+
+    Some(unsafe { std::os::fd::OwnedFd::from_raw_fd(fd as i32) })
+}
+
+#[cfg(target_os = "linux")]
+async fn wait_via_pidfd(
+    pidfd: std::os::fd::OwnedFd,
+    child: ChildHandle,
+    tx: tokio::sync::watch::Sender<Option<portable_pty::ExitStatus>>,
+) {
+    let async_fd = match tokio::io::unix::AsyncFd::new(pidfd) {
+        Ok(async_fd) => async_fd,
+        Err(_) => {
+            // Couldn't register the pidfd with the reactor; fall back to the SIGCHLD-driven
+            // waiter.
+            wait_via_sigchld(child, tx).await;
+            return;
+        }
+    };
+
+    // A pidfd becomes readable exactly once, when the process it refers to exits.
+    if async_fd.readable().await.is_ok() {
+        wait_blocking(child, tx).await;
+    }
+}
+
+#[cfg(target_os = "linux")]
+async fn wait_via_sigchld(
+    child: ChildHandle,
+    tx: tokio::sync::watch::Sender<Option<portable_pty::ExitStatus>>,
+) {
+    let status = spawn_blocking(move || -> Result<Option<portable_pty::ExitStatus>> {
+        let mut signals = signal_hook::iterator::Signals::new([signal_hook::consts::SIGCHLD])
+            .map_err(|e| eyre!("Failed to register SIGCHLD handler: {}", e))?;
+
+        loop {
+            if let Some(status) = child
+                .lock()
+                .map_err(|e| eyre!("Failed to lock pty child: {e}"))?
+                .try_wait()
+                .map_err(|e| eyre!("Failed to check child process status: {}", e))?
+            {
+                return Ok(Some(status));
+            }
+
+            // Block until the next SIGCHLD anywhere in the process, then re-check our own
+            // child; avoids a fixed-interval sleep loop.
+            if signals.wait().next().is_none() {
+                return Ok(None);
+            }
+        }
+    })
+    .await;
+
+    if let Ok(Ok(Some(status))) = status {
+        let _ = tx.send(Some(status));
+    }
+}
+
+/// Wraps the pty's single reader to watch for the shell-integration hook's OSC 133 prompt
+/// markers (`ESC ] 133 ; <letter> BEL`, the de-facto shell-integration escape sequence shells
+/// like bash/zsh's preexec hooks emit) as bytes flow through to whoever is actually consuming
+/// `Pty::reader`, flipping `ready_tx` to `true` the first time a prompt marker (`A`, new prompt,
+/// or `B`, prompt drawn and awaiting input) is seen. This observes the real output stream
+/// in-place instead of racing a second reader over the same pty fd.
+struct PromptMarkerTee {
+    inner: Box<dyn std::io::Read + Send>,
+    scanner: OscMarkerScanner,
+    ready_tx: tokio::sync::watch::Sender<bool>,
+}
+
+impl std::io::Read for PromptMarkerTee {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+
+        if n > 0 && !*self.ready_tx.borrow() {
+            for marker in self.scanner.feed(&buf[..n]) {
+                if matches!(marker, b'A' | b'B') {
+                    let _ = self.ready_tx.send(true);
+                    break;
+                }
+            }
+        }
+
+        Ok(n)
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+enum ScanState {
+    #[default]
+    Ground,
+    /// Just saw `ESC`; the next byte decides what kind of sequence this is.
+    Escape,
+    /// Inside `ESC [ ... ` (CSI), e.g. the `ESC[?2004h`/`ESC[0m` sequences a shell prints around
+    /// a prompt. Ends at the first "final byte" in `0x40..=0x7e`.
+    Csi,
+    /// Inside `ESC ] ... ` (OSC), e.g. our `133;A` marker. Ends at `BEL` or `ESC \` (ST).
+    Osc,
+}
+
+/// Incrementally scans a byte stream for OSC 133 shell-integration sequences, since a marker
+/// can be split across two `read` calls.
+///
+/// Prompts routinely emit other escape sequences immediately before the OSC 133 marker (e.g.
+/// `ESC[?2004h` to enable bracketed paste, `ESC[0m` to reset SGR), so this has to actually
+/// understand CSI/OSC framing rather than just waiting for a terminator: if it didn't, an
+/// unterminated CSI sequence would swallow the OSC marker that follows it.
+#[derive(Default)]
+struct OscMarkerScanner {
+    state: ScanState,
+    pending: Vec<u8>,
+}
+
+impl OscMarkerScanner {
+    /// Feed newly-read bytes through the scanner, returning the marker letter (e.g. `A`, `B`,
+    /// `C` or `D`; whatever byte follows `133;`) of every complete OSC 133 sequence found in
+    /// `chunk`.
+    fn feed(&mut self, chunk: &[u8]) -> Vec<u8> {
+        const ESC: u8 = 0x1b;
+        const BEL: u8 = 0x07;
+        // Cap how long we'll buffer an unterminated escape sequence so a pty that never sends
+        // a terminator can't grow this unboundedly.
+        const MAX_PENDING: usize = 64;
+
+        let mut markers = Vec::new();
+
+        for &byte in chunk {
+            // A fresh ESC always (re)starts a new sequence, even mid-sequence: chained
+            // sequences like `ESC[0mESC]133;A` must not let the first, unterminated-looking
+            // CSI byte absorb the OSC that immediately follows it.
+            if byte == ESC {
+                // Inside an OSC, this ESC opens its `ESC \` (ST) terminator, so the OSC is
+                // complete here; the trailing `\` is then consumed as a plain two-byte escape.
+                if self.state == ScanState::Osc {
+                    if let Some(marker) = Self::parse_osc_133(&self.pending) {
+                        markers.push(marker);
+                    }
+                }
+                self.state = ScanState::Escape;
+                self.pending.clear();
+                self.pending.push(byte);
+                continue;
+            }
+
+            match self.state {
+                ScanState::Ground => continue,
+                ScanState::Escape => {
+                    self.pending.push(byte);
+                    self.state = match byte {
+                        b'[' => ScanState::Csi,
+                        b']' => ScanState::Osc,
+                        // Any other two-byte escape (`ESC=`, `ESC>`, ...); we don't care about
+                        // its payload, it's simply done.
+                        _ => ScanState::Ground,
+                    };
+                }
+                ScanState::Csi => {
+                    self.pending.push(byte);
+                    if (0x40..=0x7e).contains(&byte) {
+                        self.state = ScanState::Ground;
+                    }
+                }
+                ScanState::Osc => {
+                    self.pending.push(byte);
+
+                    // ST-terminated OSCs are completed by the ESC branch above.
+                    if byte == BEL {
+                        if let Some(marker) = Self::parse_osc_133(&self.pending) {
+                            markers.push(marker);
+                        }
+                        self.state = ScanState::Ground;
+                    }
+                }
+            }
+
+            if self.state == ScanState::Ground || self.pending.len() > MAX_PENDING {
+                self.state = ScanState::Ground;
+                self.pending.clear();
+            }
+        }
+
+        markers
+    }
+
+    fn parse_osc_133(seq: &[u8]) -> Option<u8> {
+        seq.strip_prefix(b"\x1b]133;")?.first().copied()
+    }
 }
@@ -0,0 +1,354 @@
+use async_trait::async_trait;
+use bytes::Bytes;
+use eyre::{eyre, Result};
+use portable_pty::{Child, ExitStatus, MasterPty, PtySize};
+use std::{
+    collections::HashMap,
+    io::Write,
+    sync::{Arc, Mutex},
+};
+use tokio::time::{sleep, Duration};
+use wezterm_ssh::{Config, Session, SessionEvent, Signal, SshChildProcess};
+
+use crate::pty::PtyMetadata;
+use crate::runtime::pty_store::PtyLike;
+
+/// How long to wait after `SIGTERM` before escalating to `SIGKILL` in `kill_child`, mirroring
+/// the local `Pty`'s grace period.
+const KILL_GRACE_PERIOD: Duration = Duration::from_millis(500);
+
+/// Single-quote `s` for safe interpolation into a remote shell command line, escaping any
+/// embedded single quotes the way `sh` expects (`'\''`).
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// A `PtyLike` that runs a runbook block's shell on a remote host instead of locally.
+///
+/// This mirrors `Pty`'s layout: `wezterm-ssh` hands back the same `MasterPty`/`Child` traits
+/// `portable_pty` uses locally, so the reader, the writer task and resize work the same way
+/// between the two and locality stays transparent to the rest of the runtime. It differs in
+/// that `child` is the concrete SSH type (see below), there is no OSC 133 prompt tracking, and
+/// `kill_child` and the job-control methods go through the SSH signal channel.
+pub struct SshPty {
+    tx: tokio::sync::mpsc::Sender<Bytes>,
+
+    pub metadata: PtyMetadata,
+    pub master: Arc<Mutex<Box<dyn MasterPty + Send>>>,
+    pub reader: Arc<Mutex<Box<dyn std::io::Read + Send>>>,
+
+    /// Kept as the concrete `wezterm-ssh` type, rather than boxed as `dyn portable_pty::Child`
+    /// like the local `Pty` does, because job-control signals need the SSH-specific `signal()`
+    /// channel request that isn't part of the `portable_pty::Child` trait.
+    pub child: Arc<Mutex<SshChildProcess>>,
+
+    exit_rx: tokio::sync::watch::Receiver<Option<ExitStatus>>,
+}
+
+#[async_trait]
+impl PtyLike for SshPty {
+    fn metadata(&self) -> PtyMetadata {
+        self.metadata.clone()
+    }
+
+    async fn kill_child(&self) -> Result<()> {
+        self.kill_child().await
+    }
+
+    async fn send_bytes(&self, bytes: Bytes) -> Result<()> {
+        self.send_bytes(bytes).await
+    }
+
+    async fn resize(&self, rows: u16, cols: u16) -> Result<()> {
+        self.resize(rows, cols).await
+    }
+
+    async fn wait(&self) -> Result<ExitStatus> {
+        self.wait().await
+    }
+
+    async fn interrupt(&self) -> Result<()> {
+        self.send_signal(Signal::INT).await
+    }
+
+    async fn terminate(&self) -> Result<()> {
+        self.send_signal(Signal::TERM).await
+    }
+
+    async fn suspend(&self) -> Result<()> {
+        self.send_signal(Signal::TSTP).await
+    }
+
+    async fn resume(&self) -> Result<()> {
+        self.send_signal(Signal::CONT).await
+    }
+}
+
+impl SshPty {
+    pub async fn open(
+        host: String,
+        user: String,
+        port: Option<u16>,
+        rows: u16,
+        cols: u16,
+        cwd: Option<String>,
+        env: HashMap<String, String>,
+        metadata: PtyMetadata,
+        shell: Option<String>,
+    ) -> Result<Self> {
+        let mut config = Config::new();
+        config.add_default_config_files();
+
+        let mut overrides = HashMap::new();
+        overrides.insert("user".to_string(), user);
+        if let Some(port) = port {
+            overrides.insert("port".to_string(), port.to_string());
+        }
+        let config = config.for_host(&host, overrides);
+
+        let (session, events) = Session::connect(config)
+            .map_err(|e| eyre!("Failed to start SSH session to {host}: {e}"))?;
+
+        wait_until_authenticated(&events, &host).await?;
+
+        let pty_size = PtySize {
+            rows,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        };
+
+        // The third `request_pty` argument is the command line to run under the pty, not a
+        // working directory - we build that command ourselves below and pass it to `exec`
+        // instead, so nothing is executed here.
+        let (ssh_pty, child) = session
+            .request_pty("xterm-256color", pty_size, None, Some(env))
+            .await
+            .map_err(|e| eyre!("Failed to open remote pty on {host}: {e}"))?;
+
+        let mut cmd = match shell {
+            Some(shell_path) if !shell_path.is_empty() => shell_path,
+            _ => "$SHELL -i".to_string(),
+        };
+        // Flags to our shell integration that this is running within the desktop app, same as
+        // the local path.
+        cmd = format!("ATUIN_DESKTOP_PTY=true TERM=xterm-256color {cmd}");
+
+        // `request_pty` has no cwd of its own, so `cd` into it ourselves before handing off to
+        // the shell.
+        if let Some(cwd) = &cwd {
+            cmd = format!("cd {} && {cmd}", shell_quote(cwd));
+        }
+
+        let child = child
+            .exec(&cmd, None)
+            .await
+            .map_err(|e| eyre!("Failed to spawn remote shell on {host}: {e}"))?;
+
+        // Handle input -> write to master writer, identical to the local `Pty`.
+        let (master_tx, mut master_rx) = tokio::sync::mpsc::channel::<Bytes>(32);
+
+        let mut writer = ssh_pty
+            .take_writer()
+            .map_err(|e| eyre!("Failed to take writer for remote pty: {e}"))?;
+        let reader = ssh_pty
+            .try_clone_reader()
+            .map_err(|e| eyre!("Failed to clone reader for remote pty: {e}"))?;
+
+        tokio::spawn(async move {
+            while let Some(bytes) = master_rx.recv().await {
+                writer.write_all(&bytes).unwrap();
+                writer.flush().unwrap();
+            }
+
+            // Closing the writer sends EOF to the remote shell, same as the local path.
+            drop(writer);
+        });
+
+        let child = Arc::new(Mutex::new(child));
+        let exit_rx = spawn_reaper(Arc::clone(&child));
+
+        Ok(SshPty {
+            metadata,
+            tx: master_tx,
+            master: Arc::new(Mutex::new(ssh_pty)),
+            reader: Arc::new(Mutex::new(reader)),
+            child,
+            exit_rx,
+        })
+    }
+
+    pub async fn resize(&self, rows: u16, cols: u16) -> Result<()> {
+        let master = self
+            .master
+            .lock()
+            .map_err(|e| eyre!("Failed to lock pty master: {e}"))?;
+
+        master
+            .resize(PtySize {
+                rows,
+                cols,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(|e| eyre!("Failed to resize remote terminal: {e}"))?;
+
+        Ok(())
+    }
+
+    pub async fn send_bytes(&self, bytes: Bytes) -> Result<()> {
+        self.tx
+            .send(bytes)
+            .await
+            .map_err(|e| eyre!("Failed to write to master tx: {}", e))
+    }
+
+    /// Kill the remote shell and everything it spawned, not just the shell process itself.
+    ///
+    /// `Child::kill()` only reaches the immediate remote command, so instead this delivers the
+    /// same `TERM`-then-`KILL` escalation the local `Pty::kill_child` does, but via the SSH
+    /// signal channel, which `sshd` routes to whatever currently owns the remote pty's
+    /// foreground process group - including any jobs (`make`, `docker`, `ssh`, ...) the shell
+    /// launched, not just the shell itself.
+    pub async fn kill_child(&self) -> Result<()> {
+        self.send_signal(Signal::TERM).await?;
+
+        sleep(KILL_GRACE_PERIOD).await;
+
+        if self.is_child_running()? {
+            self.send_signal(Signal::KILL).await?;
+        }
+
+        Ok(())
+    }
+
+    fn is_child_running(&self) -> Result<bool> {
+        let mut child = self
+            .child
+            .lock()
+            .map_err(|e| eyre!("Failed to lock pty child: {e}"))?;
+
+        match child.try_wait() {
+            Ok(Some(_)) => Ok(false),
+            Ok(None) => Ok(true),
+            Err(e) => Err(eyre!("Failed to check remote child status: {}", e)),
+        }
+    }
+
+    pub async fn wait(&self) -> Result<ExitStatus> {
+        let mut rx = self.exit_rx.clone();
+
+        loop {
+            if let Some(status) = rx.borrow().clone() {
+                return Ok(status);
+            }
+
+            rx.changed()
+                .await
+                .map_err(|_| eyre!("Exit status watcher dropped before the child exited"))?;
+        }
+    }
+
+    /// Deliver a job-control signal to the remote shell's foreground process group via the
+    /// SSH protocol's own `signal` channel request (RFC 4254 §6.9); the remote `sshd` is
+    /// responsible for routing it to whatever currently owns the pty, same as a local terminal
+    /// driver would via the foreground pgid.
+    async fn send_signal(&self, sig: Signal) -> Result<()> {
+        let child = self
+            .child
+            .lock()
+            .map_err(|e| eyre!("Failed to lock pty child: {e}"))?;
+
+        child
+            .signal(sig)
+            .map_err(|e| eyre!("Failed to deliver signal to remote child: {e}"))
+    }
+}
+
+/// Drain SSH session events until the session is authenticated, surfacing the first error (bad
+/// host key, failed auth, ...) as our own `Result`.
+async fn wait_until_authenticated(
+    events: &smol::channel::Receiver<SessionEvent>,
+    host: &str,
+) -> Result<()> {
+    while let Ok(event) = events.recv().await {
+        match event {
+            SessionEvent::Authenticated => return Ok(()),
+            SessionEvent::Error(e) => {
+                return Err(eyre!("SSH session to {host} failed: {e}"));
+            }
+            SessionEvent::HostVerify(verify) => {
+                // `message` comes from libssh2's own `known_hosts` check, so it already tells us
+                // apart a host we've simply never seen (fine to trust on first use) from one
+                // whose key no longer matches what we have on record (a changed/MITM'd key,
+                // which we must never auto-accept).
+                let message = verify.message.clone();
+                if is_host_key_changed(&message) {
+                    verify.answer(false).await.ok();
+                    return Err(eyre!(
+                        "Refusing to connect to {host}: host key does not match known_hosts ({message})"
+                    ));
+                }
+
+                log::warn!("Trusting new host key for {host} on first use: {message}");
+                verify
+                    .answer(true)
+                    .await
+                    .map_err(|e| eyre!("Failed to accept host key for {host}: {e}"))?;
+            }
+            SessionEvent::Authenticate(auth) => {
+                auth.answer(Vec::new())
+                    .await
+                    .map_err(|e| eyre!("Failed to answer auth challenge for {host}: {e}"))?;
+            }
+            _ => {}
+        }
+    }
+
+    Err(eyre!("SSH session to {host} closed before authenticating"))
+}
+
+/// Whether a `HostVerify` message describes a key that no longer matches `known_hosts`, as
+/// opposed to one we've simply never recorded before.
+fn is_host_key_changed(message: &str) -> bool {
+    let message = message.to_ascii_uppercase();
+    message.contains("HOST IDENTIFICATION HAS CHANGED") || message.contains("MISMATCH")
+}
+
+type ChildHandle = Arc<Mutex<SshChildProcess>>;
+
+/// Reap the remote child in the background and publish its exit status.
+///
+/// `kill_child` and `send_signal` (interrupt/terminate/suspend/resume) also need
+/// `self.child.lock()` while the remote command is running, so this deliberately avoids calling
+/// the blocking `Child::wait()` while holding the guard — that would hold the lock for the
+/// entire life of the remote shell and make job control over SSH impossible. Instead we poll
+/// `try_wait`, releasing the lock between checks, same as the local `Pty`'s non-Linux fallback.
+fn spawn_reaper(child: ChildHandle) -> tokio::sync::watch::Receiver<Option<ExitStatus>> {
+    const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+    let (tx, rx) = tokio::sync::watch::channel(None);
+
+    tokio::spawn(async move {
+        loop {
+            let status = {
+                let mut child = match child.lock() {
+                    Ok(child) => child,
+                    Err(_) => return,
+                };
+                child.try_wait()
+            };
+
+            match status {
+                Ok(Some(status)) => {
+                    let _ = tx.send(Some(status));
+                    return;
+                }
+                Ok(None) => sleep(POLL_INTERVAL).await,
+                Err(_) => return,
+            }
+        }
+    });
+
+    rx
+}